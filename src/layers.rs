@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use xml::{attribute::OwnedAttribute, EventReader};
+
+use crate::{
+    error::TiledError,
+    util::{get_attrs, parse_data_line, Compression, Encoding},
+};
+
+/// The decoded tile data for a layer: either one contiguous grid, or, for
+/// infinite maps, a sparse set of chunks keyed by their origin.
+#[derive(Debug, Clone)]
+pub enum LayerData {
+    Finite(Vec<Vec<LayerTile>>),
+    Infinite(HashMap<(i32, i32), Chunk>),
+}
+
+/// A single chunk of tile data within an infinite layer.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub tiles: Vec<Vec<LayerTile>>,
+}
+
+impl Chunk {
+    pub(crate) fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        encoding: Option<Encoding>,
+        compression: Compression,
+        warnings: &mut Vec<TiledError>,
+    ) -> Result<Chunk, TiledError> {
+        let ((), (x, y, width, height)) = get_attrs!(
+            attrs,
+            optionals: [],
+            required: [
+                ("x", x, |v: String| v.parse().ok()),
+                ("y", y, |v: String| v.parse().ok()),
+                ("width", width, |v: String| v.parse().ok()),
+                ("height", height, |v: String| v.parse().ok()),
+            ],
+            TiledError::MalformedAttributes("chunk must have x, y, width and height".to_string())
+        );
+
+        let tiles = parse_data_line(encoding, compression, parser, width, "chunk", warnings)?;
+
+        Ok(Chunk {
+            x,
+            y,
+            width,
+            height,
+            tiles,
+        })
+    }
+}
+
+/// A single tile placed within a layer. The raw GID read off the map packs
+/// both the tile's id and its flip/rotation state; `new` takes them already
+/// split apart so callers never have to mask the id themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LayerTile {
+    pub gid: u32,
+    pub flip_h: bool,
+    pub flip_v: bool,
+    pub flip_d: bool,
+    pub flip_hex120: bool,
+}
+
+impl LayerTile {
+    pub fn new(gid: u32, flip_h: bool, flip_v: bool, flip_d: bool, flip_hex120: bool) -> Self {
+        LayerTile {
+            gid,
+            flip_h,
+            flip_v,
+            flip_d,
+            flip_hex120,
+        }
+    }
+}
@@ -0,0 +1,13 @@
+mod animation;
+mod error;
+mod layers;
+mod map;
+mod tileset;
+mod util;
+
+pub use animation::Frame;
+pub use error::TiledError;
+pub use layers::{Chunk, LayerData, LayerTile};
+pub use map::Map;
+pub use tileset::Tileset;
+pub use util::{Compression, Encoding};
@@ -57,8 +57,9 @@ macro_rules! parse_tag {
 use std::{
     collections::HashMap,
     fs::File,
-    io::{BufReader, Read},
+    io::Read,
     path::Path,
+    str::FromStr,
 };
 
 pub(crate) use get_attrs;
@@ -73,6 +74,76 @@ use crate::{
     tileset::Tileset,
 };
 
+/// The `encoding` a layer's `<data>` element declares its tiles are stored
+/// in. Absent entirely, the tiles are written as plain `<tile gid="..."/>`
+/// children instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Base64,
+    Csv,
+}
+
+impl FromStr for Encoding {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base64" => Ok(Encoding::Base64),
+            "csv" => Ok(Encoding::Csv),
+            _ => Err(TiledError::Other(format!("Unknown encoding format {}", s))),
+        }
+    }
+}
+
+/// The `compression` applied to a layer's `<data>` on top of its `Encoding`.
+/// `None` is the default when the attribute is absent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Zlib,
+    Gzip,
+    Zstd,
+}
+
+impl FromStr for Compression {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zlib" => Ok(Compression::Zlib),
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(TiledError::Other(format!(
+                "Unknown compression format {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Bits 32, 31, 30 and 29 of a GID store the tile's transform state rather
+/// than its id; mask them off and surface them as flags on the `LayerTile`.
+const FLIPPED_HORIZONTALLY_FLAG: u32 = 0x80000000;
+const FLIPPED_VERTICALLY_FLAG: u32 = 0x40000000;
+const FLIPPED_DIAGONALLY_FLAG: u32 = 0x20000000;
+const ROTATED_HEXAGONAL_120_FLAG: u32 = 0x10000000;
+
+/// Splits a raw GID into its tile id and flip/rotation flags and builds the
+/// corresponding `LayerTile`. Used by every tile-data format so the flags are
+/// decoded consistently regardless of how the data was encoded.
+fn build_layer_tile(bits: u32) -> LayerTile {
+    let flip_h = bits & FLIPPED_HORIZONTALLY_FLAG != 0;
+    let flip_v = bits & FLIPPED_VERTICALLY_FLAG != 0;
+    let flip_d = bits & FLIPPED_DIAGONALLY_FLAG != 0;
+    let flip_hex120 = bits & ROTATED_HEXAGONAL_120_FLAG != 0;
+    let gid = bits
+        & !(FLIPPED_HORIZONTALLY_FLAG
+            | FLIPPED_VERTICALLY_FLAG
+            | FLIPPED_DIAGONALLY_FLAG
+            | ROTATED_HEXAGONAL_120_FLAG);
+    LayerTile::new(gid, flip_h, flip_v, flip_d, flip_hex120)
+}
+
 pub(crate) fn parse_animation<R: Read>(
     parser: &mut EventReader<R>,
 ) -> Result<Vec<Frame>, TiledError> {
@@ -90,8 +161,9 @@ pub(crate) fn parse_infinite_data<R: Read>(
     parser: &mut EventReader<R>,
     attrs: Vec<OwnedAttribute>,
     width: u32,
+    warnings: &mut Vec<TiledError>,
 ) -> Result<LayerData, TiledError> {
-    let ((e, c), ()) = get_attrs!(
+    let ((encoding, compression), ()) = get_attrs!(
         attrs,
         optionals: [
             ("encoding", encoding, |v| Some(v)),
@@ -100,11 +172,16 @@ pub(crate) fn parse_infinite_data<R: Read>(
         required: [],
         TiledError::MalformedAttributes("data must have an encoding and a compression".to_string())
     );
+    let encoding: Option<Encoding> = encoding.map(|e: String| e.parse()).transpose()?;
+    let compression: Compression = compression
+        .map(|c: String| c.parse())
+        .transpose()?
+        .unwrap_or(Compression::None);
 
     let mut chunks = HashMap::<(i32, i32), Chunk>::new();
     parse_tag!(parser, "data", {
         "chunk" => |attrs| {
-            let chunk = Chunk::new(parser, attrs, e.clone(), c.clone())?;
+            let chunk = Chunk::new(parser, attrs, encoding, compression, warnings)?;
             chunks.insert((chunk.x, chunk.y), chunk);
             Ok(())
         }
@@ -117,8 +194,9 @@ pub(crate) fn parse_data<R: Read>(
     parser: &mut EventReader<R>,
     attrs: Vec<OwnedAttribute>,
     width: u32,
+    warnings: &mut Vec<TiledError>,
 ) -> Result<LayerData, TiledError> {
-    let ((e, c), ()) = get_attrs!(
+    let ((encoding, compression), ()) = get_attrs!(
         attrs,
         optionals: [
             ("encoding", encoding, |v| Some(v)),
@@ -127,113 +205,276 @@ pub(crate) fn parse_data<R: Read>(
         required: [],
         TiledError::MalformedAttributes("data must have an encoding and a compression".to_string())
     );
+    let encoding: Option<Encoding> = encoding.map(|e: String| e.parse()).transpose()?;
+    let compression: Compression = compression
+        .map(|c: String| c.parse())
+        .transpose()?
+        .unwrap_or(Compression::None);
 
-    let tiles = parse_data_line(e, c, parser, width)?;
+    let tiles = parse_data_line(encoding, compression, parser, width, "data", warnings)?;
 
     Ok(LayerData::Finite(tiles))
 }
 
 pub(crate) fn parse_data_line<R: Read>(
-    encoding: Option<String>,
-    compression: Option<String>,
+    encoding: Option<Encoding>,
+    compression: Compression,
     parser: &mut EventReader<R>,
     width: u32,
+    close_tag: &'static str,
+    warnings: &mut Vec<TiledError>,
 ) -> Result<Vec<Vec<LayerTile>>, TiledError> {
     match (encoding, compression) {
-        (None, None) => {
-            return Err(TiledError::Other(
-                "XML format is currently not supported".to_string(),
-            ))
+        (None, Compression::None) => {
+            let mut tiles = Vec::new();
+            parse_tag!(parser, close_tag, {
+                "tile" => |attrs: Vec<OwnedAttribute>| {
+                    let gid = attrs
+                        .into_iter()
+                        .find(|attr| attr.name.local_name == "gid")
+                        .and_then(|attr| attr.value.parse().ok())
+                        .unwrap_or(0);
+                    tiles.push(build_layer_tile(gid));
+                    Ok(())
+                },
+            });
+            Ok(if width == 0 {
+                if tiles.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![tiles]
+                }
+            } else {
+                tiles.chunks(width as usize).map(|c| c.to_vec()).collect()
+            })
         }
-        (Some(e), None) => match e.as_ref() {
-            "base64" => return parse_base64(parser).map(|v| convert_to_tile(&v, width)),
-            "csv" => return decode_csv(width, parser),
-            e => return Err(TiledError::Other(format!("Unknown encoding format {}", e))),
-        },
-        (Some(e), Some(c)) => match (e.as_ref(), c.as_ref()) {
-            ("base64", "zlib") => {
-                return parse_base64(parser)
-                    .and_then(decode_zlib)
-                    .map(|v| convert_to_tile(&v, width))
+        (None, c) => Err(TiledError::Other(format!(
+            "XML tile data cannot be combined with {:?} compression",
+            c
+        ))),
+        (Some(Encoding::Csv), Compression::None) => decode_csv(width, parser, close_tag),
+        (Some(Encoding::Base64), declared) => {
+            let mut reader = Base64CharacterReader::new(parser, close_tag);
+            let header = peek_header(&mut reader, 4)?;
+            let (compression, warning) = resolve_compression(declared, &header);
+            if let Some(warning) = warning {
+                warnings.push(warning);
             }
-            ("base64", "gzip") => {
-                return parse_base64(parser)
-                    .and_then(decode_gzip)
-                    .map(|v| convert_to_tile(&v, width))
-            }
-            #[cfg(feature = "zstd")]
-            ("base64", "zstd") => {
-                return parse_base64(parser)
-                    .and_then(decode_zstd)
-                    .map(|v| convert_to_tile(&v, width))
-            }
-            (e, c) => {
-                return Err(TiledError::Other(format!(
-                    "Unknown combination of {} encoding and {} compression",
-                    e, c
-                )))
+            let reader = std::io::Cursor::new(header).chain(reader);
+            match compression {
+                Compression::None => build_tiles_from_reader(reader, width),
+                Compression::Zlib => {
+                    let reader = libflate::zlib::Decoder::new(reader)
+                        .map_err(TiledError::DecompressingError)?;
+                    build_tiles_from_reader(reader, width)
+                }
+                Compression::Gzip => {
+                    let reader = libflate::gzip::Decoder::new(reader)
+                        .map_err(TiledError::DecompressingError)?;
+                    build_tiles_from_reader(reader, width)
+                }
+                #[cfg(feature = "zstd")]
+                Compression::Zstd => {
+                    let reader = zstd::stream::read::Decoder::new(reader)
+                        .map_err(TiledError::DecompressingError)?;
+                    build_tiles_from_reader(reader, width)
+                }
+                #[cfg(not(feature = "zstd"))]
+                Compression::Zstd => Err(TiledError::Other(
+                    "zstd compression requires the zstd feature".to_string(),
+                )),
             }
-        },
-        _ => return Err(TiledError::Other("Missing encoding format".to_string())),
-    };
+        }
+        (e, c) => Err(TiledError::Other(format!(
+            "Unknown combination of {:?} encoding and {:?} compression",
+            e, c
+        ))),
+    }
 }
 
-pub(crate) fn parse_base64<R: Read>(parser: &mut EventReader<R>) -> Result<Vec<u8>, TiledError> {
-    loop {
-        match parser.next().map_err(TiledError::XmlDecodingError)? {
-            XmlEvent::Characters(s) => {
-                return base64::decode(s.trim().as_bytes()).map_err(TiledError::Base64DecodingError)
-            }
-            XmlEvent::EndElement { name, .. } => {
-                if name.local_name == "data" {
-                    return Ok(Vec::new());
-                }
-            }
-            _ => {}
+/// Recognises a compressed stream from its leading bytes regardless of what
+/// the `compression` attribute (if any) claims, so a missing or wrong
+/// attribute doesn't turn into a confusing decode failure.
+fn sniff_compression(header: &[u8]) -> Option<Compression> {
+    if header.len() >= 2 && header[0] == 0x1f && header[1] == 0x8b {
+        return Some(Compression::Gzip);
+    }
+    if header.len() >= 4 && header[0..4] == [0x28, 0xB5, 0x2F, 0xFD] {
+        return Some(Compression::Zstd);
+    }
+    if !header.is_empty() && header[0] & 0x0f == 8 && header.len() >= 2 {
+        let cmf_flg = ((header[0] as u32) << 8) | header[1] as u32;
+        if cmf_flg % 31 == 0 {
+            return Some(Compression::Zlib);
+        }
+    }
+    None
+}
+
+/// Reconciles the declared `compression` with what the stream's header bytes
+/// actually look like: the detected format always wins (so the loader keeps
+/// working against exports with missing or wrong `compression` attributes),
+/// and a mismatch against `declared` is returned as a non-fatal warning for
+/// the caller to surface however it sees fit.
+fn resolve_compression(
+    declared: Compression,
+    header: &[u8],
+) -> (Compression, Option<TiledError>) {
+    match sniff_compression(header) {
+        Some(detected) if detected != declared => (
+            detected,
+            Some(TiledError::Other(format!(
+                "data declared {:?} compression but its contents look like {:?}; using the detected format",
+                declared, detected
+            ))),
+        ),
+        Some(detected) => (detected, None),
+        None => (declared, None),
+    }
+}
+
+/// Reads up to `len` bytes from `reader` without losing them, so they can be
+/// chained back in front of the stream once a peek has been taken.
+fn peek_header<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>, TiledError> {
+    let mut header = vec![0u8; len];
+    let mut filled = 0;
+    while filled < len {
+        let n = reader.read(&mut header[filled..]).map_err(io_error_to_tiled)?;
+        if n == 0 {
+            break;
         }
+        filled += n;
     }
+    header.truncate(filled);
+    Ok(header)
 }
 
-pub(crate) fn decode_zlib(data: Vec<u8>) -> Result<Vec<u8>, TiledError> {
-    use libflate::zlib::Decoder;
-    let mut zd =
-        Decoder::new(BufReader::new(&data[..])).map_err(|e| TiledError::DecompressingError(e))?;
-    let mut data = Vec::new();
-    match zd.read_to_end(&mut data) {
-        Ok(_v) => {}
-        Err(e) => return Err(TiledError::DecompressingError(e)),
+/// Converts an `io::Error` bubbling up from a tile-data `Read` chain back
+/// into the right `TiledError` variant. `Base64CharacterReader` tags invalid
+/// base64 with `ErrorKind::InvalidData` and the original `base64::DecodeError`
+/// as its source, so that failure is reported as `Base64DecodingError`
+/// instead of being misreported as a decompression failure.
+fn io_error_to_tiled(e: std::io::Error) -> TiledError {
+    let kind = e.kind();
+    if kind == std::io::ErrorKind::InvalidData {
+        match e.into_inner() {
+            Some(inner) => {
+                return match inner.downcast::<base64::DecodeError>() {
+                    Ok(decode_err) => TiledError::Base64DecodingError(*decode_err),
+                    Err(inner) => {
+                        TiledError::DecompressingError(std::io::Error::new(kind, inner))
+                    }
+                };
+            }
+            None => return TiledError::DecompressingError(std::io::Error::from(kind)),
+        }
     }
-    Ok(data)
+    TiledError::DecompressingError(e)
+}
+
+/// Adapts the `Characters` events of a layer's `<data>` element into a
+/// `Read` stream of base64-decoded bytes, decoding chunk by chunk as the XML
+/// parser produces them instead of buffering the whole layer up front. A
+/// decompressing `Decoder` can then wrap this directly, since all of
+/// `libflate`'s and `zstd`'s decoders also implement `Read`.
+struct Base64CharacterReader<'a, R: Read> {
+    parser: &'a mut EventReader<R>,
+    close_tag: &'static str,
+    pending: Vec<u8>,
+    offset: usize,
+    done: bool,
 }
 
-pub(crate) fn decode_gzip(data: Vec<u8>) -> Result<Vec<u8>, TiledError> {
-    use libflate::gzip::Decoder;
-    let mut zd =
-        Decoder::new(BufReader::new(&data[..])).map_err(|e| TiledError::DecompressingError(e))?;
+impl<'a, R: Read> Base64CharacterReader<'a, R> {
+    fn new(parser: &'a mut EventReader<R>, close_tag: &'static str) -> Self {
+        Base64CharacterReader {
+            parser,
+            close_tag,
+            pending: Vec::new(),
+            offset: 0,
+            done: false,
+        }
+    }
 
-    let mut data = Vec::new();
-    zd.read_to_end(&mut data)
-        .map_err(|e| TiledError::DecompressingError(e))?;
-    Ok(data)
+    fn fill_pending(&mut self) -> std::io::Result<()> {
+        while self.offset >= self.pending.len() && !self.done {
+            match self
+                .parser
+                .next()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
+            {
+                XmlEvent::Characters(s) => {
+                    self.pending = base64::decode(s.trim().as_bytes())
+                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                    self.offset = 0;
+                }
+                XmlEvent::EndElement { name, .. } if name.local_name == self.close_tag => {
+                    self.done = true;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
 }
 
-#[cfg(feature = "zstd")]
-pub(crate) fn decode_zstd(data: Vec<u8>) -> Result<Vec<u8>, TiledError> {
-    use std::io::Cursor;
-    use zstd::stream::read::Decoder;
+impl<'a, R: Read> Read for Base64CharacterReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.fill_pending()?;
+        let available = &self.pending[self.offset..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.offset += n;
+        Ok(n)
+    }
+}
 
-    let buff = Cursor::new(&data);
-    let mut zd = Decoder::with_buffer(buff).map_err(|e| TiledError::DecompressingError(e))?;
+/// Reads 4 bytes at a time off `reader` and emits `LayerTile`s row by row,
+/// so no byte buffer beyond the current tile's 4 bytes is kept here; every
+/// finished row is kept and the whole grid is returned.
+fn build_tiles_from_reader<R: Read>(
+    mut reader: R,
+    width: u32,
+) -> Result<Vec<Vec<LayerTile>>, TiledError> {
+    let mut rows = Vec::new();
+    let mut row = Vec::with_capacity(width as usize);
+    let mut buf = [0u8; 4];
+    while read_tile_bytes(&mut reader, &mut buf)? {
+        let bits = u32::from_le_bytes(buf);
+        row.push(build_layer_tile(bits));
+        if row.len() == width as usize {
+            rows.push(std::mem::replace(&mut row, Vec::with_capacity(width as usize)));
+        }
+    }
+    if !row.is_empty() {
+        rows.push(row);
+    }
+    Ok(rows)
+}
 
-    let mut data = Vec::new();
-    zd.read_to_end(&mut data)
-        .map_err(|e| TiledError::DecompressingError(e))?;
-    Ok(data)
+/// Fills `buf` from `reader`, returning `false` on a clean end of stream and
+/// an error if the stream ends partway through a tile.
+fn read_tile_bytes<R: Read>(reader: &mut R, buf: &mut [u8; 4]) -> Result<bool, TiledError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let n = reader.read(&mut buf[filled..]).map_err(io_error_to_tiled)?;
+        if n == 0 {
+            if filled == 0 {
+                return Ok(false);
+            }
+            return Err(TiledError::PrematureEnd(
+                "Tile data ended mid-tile".to_string(),
+            ));
+        }
+        filled += n;
+    }
+    Ok(true)
 }
 
 pub(crate) fn decode_csv<R: Read>(
     width: u32,
     parser: &mut EventReader<R>,
+    close_tag: &'static str,
 ) -> Result<Vec<Vec<LayerTile>>, TiledError> {
     loop {
         match parser.next().map_err(TiledError::XmlDecodingError)? {
@@ -242,7 +483,7 @@ pub(crate) fn decode_csv<R: Read>(
                     .split(&['\n', '\r', ','][0..])
                     .filter(|v| v.trim() != "")
                     .map(|v| v.parse().unwrap())
-                    .map(LayerTile::new)
+                    .map(build_layer_tile)
                     .peekable();
                 let mut rows = Vec::new();
                 while tiles_it.peek().is_some() {
@@ -252,7 +493,7 @@ pub(crate) fn decode_csv<R: Read>(
                 return Ok(rows);
             }
             XmlEvent::EndElement { name, .. } => {
-                if name.local_name == "data" {
+                if name.local_name == close_tag {
                     return Ok(Vec::new());
                 }
             }
@@ -261,22 +502,154 @@ pub(crate) fn decode_csv<R: Read>(
     }
 }
 
-pub(crate) fn convert_to_tile(all: &Vec<u8>, width: u32) -> Vec<Vec<LayerTile>> {
-    let mut data = Vec::new();
-    for chunk in all.chunks((width * 4) as usize) {
-        let mut row = Vec::new();
-        for i in 0..width {
-            let start: usize = i as usize * 4;
-            let n = ((chunk[start + 3] as u32) << 24)
-                + ((chunk[start + 2] as u32) << 16)
-                + ((chunk[start + 1] as u32) << 8)
-                + chunk[start] as u32;
-            let n = LayerTile::new(n);
-            row.push(n);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser_for(xml: &str) -> EventReader<std::io::Cursor<Vec<u8>>> {
+        EventReader::new(std::io::Cursor::new(xml.as_bytes().to_vec()))
+    }
+
+    /// Advances `parser` past the `StartElement` for `tag`, returning its attributes.
+    fn advance_to_start(
+        parser: &mut EventReader<std::io::Cursor<Vec<u8>>>,
+        tag: &str,
+    ) -> Vec<OwnedAttribute> {
+        loop {
+            match parser.next().unwrap() {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } if name.local_name == tag => return attributes,
+                XmlEvent::EndDocument => panic!("ran out of document looking for <{}>", tag),
+                _ => {}
+            }
         }
-        data.push(row);
     }
-    data
+
+    #[test]
+    fn build_layer_tile_splits_flip_and_rotation_flags_from_gid() {
+        let bits = 5
+            | FLIPPED_HORIZONTALLY_FLAG
+            | FLIPPED_VERTICALLY_FLAG
+            | FLIPPED_DIAGONALLY_FLAG
+            | ROTATED_HEXAGONAL_120_FLAG;
+        let tile = build_layer_tile(bits);
+        assert_eq!(tile.gid, 5);
+        assert!(tile.flip_h);
+        assert!(tile.flip_v);
+        assert!(tile.flip_d);
+        assert!(tile.flip_hex120);
+    }
+
+    #[test]
+    fn build_layer_tile_plain_gid_has_no_flags_set() {
+        let tile = build_layer_tile(7);
+        assert_eq!(tile.gid, 7);
+        assert!(!tile.flip_h);
+        assert!(!tile.flip_v);
+        assert!(!tile.flip_d);
+        assert!(!tile.flip_hex120);
+    }
+
+    #[test]
+    fn parse_data_line_decodes_base64_tiles() {
+        // base64::decode("AQAAAAIAAAADAAAABAAAAA==") == [1,0,0,0, 2,0,0,0, 3,0,0,0, 4,0,0,0]
+        let xml = r#"<data encoding="base64">AQAAAAIAAAADAAAABAAAAA==</data>"#;
+        let mut parser = parser_for(xml);
+        advance_to_start(&mut parser, "data");
+        let mut warnings = Vec::new();
+        let tiles =
+            parse_data_line(Some(Encoding::Base64), Compression::None, &mut parser, 2, "data", &mut warnings)
+                .unwrap();
+        assert_eq!(
+            tiles,
+            vec![
+                vec![build_layer_tile(1), build_layer_tile(2)],
+                vec![build_layer_tile(3), build_layer_tile(4)],
+            ]
+        );
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn parse_data_line_plain_xml_width_zero_does_not_panic() {
+        let xml = r#"<data><tile gid="1"/><tile gid="2"/></data>"#;
+        let mut parser = parser_for(xml);
+        advance_to_start(&mut parser, "data");
+        let mut warnings = Vec::new();
+        let tiles =
+            parse_data_line(None, Compression::None, &mut parser, 0, "data", &mut warnings)
+                .unwrap();
+        assert_eq!(tiles, vec![vec![build_layer_tile(1), build_layer_tile(2)]]);
+    }
+
+    #[test]
+    fn infinite_data_keeps_sibling_chunks_separate() {
+        let xml = r#"<data encoding="base64">
+            <chunk x="0" y="0" width="2" height="1">
+                AQAAAAIAAAA=
+            </chunk>
+            <chunk x="2" y="0" width="2" height="1">
+                AwAAAAQAAAA=
+            </chunk>
+        </data>"#;
+        let mut parser = parser_for(xml);
+        let attrs = advance_to_start(&mut parser, "data");
+        let mut warnings = Vec::new();
+        let data = parse_infinite_data(&mut parser, attrs, 2, &mut warnings).unwrap();
+        let chunks = match data {
+            LayerData::Infinite(chunks) => chunks,
+            LayerData::Finite(_) => panic!("expected infinite layer data"),
+        };
+
+        let first = &chunks[&(0, 0)];
+        assert_eq!(first.tiles, vec![vec![build_layer_tile(1), build_layer_tile(2)]]);
+        let second = &chunks[&(2, 0)];
+        assert_eq!(second.tiles, vec![vec![build_layer_tile(3), build_layer_tile(4)]]);
+    }
+
+    #[test]
+    fn sniff_compression_detects_gzip() {
+        assert_eq!(
+            sniff_compression(&[0x1f, 0x8b, 0x08, 0x00]),
+            Some(Compression::Gzip)
+        );
+    }
+
+    #[test]
+    fn sniff_compression_detects_zstd() {
+        assert_eq!(
+            sniff_compression(&[0x28, 0xB5, 0x2F, 0xFD]),
+            Some(Compression::Zstd)
+        );
+    }
+
+    #[test]
+    fn sniff_compression_detects_zlib() {
+        // CMF 0x78 / FLG 0x9c is a common zlib header: (0x78 << 8 | 0x9c) % 31 == 0.
+        assert_eq!(sniff_compression(&[0x78, 0x9c]), Some(Compression::Zlib));
+    }
+
+    #[test]
+    fn sniff_compression_none_for_raw_tile_bytes() {
+        assert_eq!(sniff_compression(&[0x01, 0x00, 0x00, 0x00]), None);
+    }
+
+    #[test]
+    fn resolve_compression_prefers_detected_and_warns_on_mismatch() {
+        let header = [0x1f, 0x8b, 0x08, 0x00];
+        let (compression, warning) = resolve_compression(Compression::None, &header);
+        assert_eq!(compression, Compression::Gzip);
+        assert!(warning.is_some());
+    }
+
+    #[test]
+    fn resolve_compression_keeps_declared_when_nothing_detected() {
+        let header = [0x01, 0x02, 0x03, 0x04];
+        let (compression, warning) = resolve_compression(Compression::Zlib, &header);
+        assert_eq!(compression, Compression::Zlib);
+        assert!(warning.is_none());
+    }
 }
 
 pub(crate) fn parse_impl<R: Read>(reader: R, map_path: Option<&Path>) -> Result<Map, TiledError> {